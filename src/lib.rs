@@ -18,27 +18,175 @@ extern crate rustc_serialize;
 
 use rustc_serialize::json;
 use std::collections::BTreeMap;
+use std::cell::Cell;
+use std::rc::Rc;
+
+/// One step along a traversal path: either an object key or an array index.
+#[derive(Clone,Debug,PartialEq,Eq)]
+pub enum PathSegment {
+    Key(String),
+    Index(usize),
+}
+
+/// Gives a handler the nesting depth and ancestor path of the value it's being called for,
+/// mirroring what `parser.stack()` tracks at that point. This enables level-based routing (act
+/// only below a certain depth, or skip whole subtrees once a sibling of interest is found)
+/// without each handler threading its own bookkeeping through recursive calls.
+pub struct PathContext {
+    segments: Vec<PathSegment>,
+}
+
+impl PathContext {
+    fn from_parser<T: Iterator<Item=char>>(parser: &json::Parser<T>) -> Self {
+        let stack = parser.stack();
+        let segments = (0..stack.len()).map(|i| match stack.get(i) {
+            json::StackElement::Key(k) => PathSegment::Key(k.to_string()),
+            json::StackElement::Index(idx) => PathSegment::Index(idx as usize),
+        }).collect();
+
+        PathContext { segments: segments }
+    }
+
+    /// The current nesting depth, i.e. how many keys/indices deep the current value sits.
+    pub fn depth(&self) -> usize {
+        self.segments.len()
+    }
+
+    /// The full path from the root down to the value currently being handled.
+    pub fn path(&self) -> &[PathSegment] {
+        &self.segments
+    }
+}
 
 /// Base callback type
 ///
 /// The callback will be given a key string for the object to analyze (when applicable),
-/// the first event to read, and a parser item to read the rest.
+/// the first event to read, a parser item to read the rest, and a `PathContext` describing where
+/// in the document this value was found.
 ///
 /// A valid handler must properly consume the json stream coming from the parser:
 /// objects should be recursively consumed, and so on.
-pub type Handler<'a,T> = Box<FnMut(String, json::JsonEvent, &mut json::Parser<T>)+'a>;
+pub type Handler<'a,T> = Box<FnMut(String, json::JsonEvent, &mut json::Parser<T>, &PathContext)+'a>;
 
 /// Creates a dummy handler that just consumes the given value
 pub fn dummy_handler<'a,T: Iterator<Item=char>>() -> Handler<'a,T> {
-    Box::new(|_,first,parser| {
+    Box::new(|_,first,parser,_| {
         // println!("Dummy read...");
         read_value(first,parser);
     })
 }
 
+/// One segment of a path pattern passed to `set_handler_at`.
+///
+/// A `Key` matches a literal object key, while `Index` (written `[]` in the path string) matches
+/// any array index.
+#[derive(PartialEq,Eq,PartialOrd,Ord,Clone,Debug)]
+enum PathKey {
+    Key(String),
+    Index,
+}
+
+/// Parses a path like `"response.items[].name"` into a sequence of `PathKey`s.
+fn parse_path(path: &str) -> Vec<PathKey> {
+    let mut segments = Vec::new();
+
+    for part in path.split('.') {
+        let mut rest = part;
+        let mut indices = 0;
+        while rest.ends_with("[]") {
+            indices += 1;
+            rest = &rest[..rest.len()-2];
+        }
+
+        if !rest.is_empty() {
+            segments.push(PathKey::Key(rest.to_string()));
+        }
+        for _ in 0..indices {
+            segments.push(PathKey::Index);
+        }
+    }
+
+    segments
+}
+
+/// A node in the trie of path patterns registered through `set_handler_at`.
+struct PathNode<'a,T> {
+    handler: Option<Handler<'a,T>>,
+    children: BTreeMap<PathKey,PathNode<'a,T>>,
+}
+
+impl <'a,T> PathNode<'a,T> {
+    fn new() -> Self {
+        PathNode {
+            handler: None,
+            children: BTreeMap::new(),
+        }
+    }
+
+    fn insert(&mut self, path: &[PathKey], handler: Handler<'a,T>) {
+        match path.split_first() {
+            None => self.handler = Some(handler),
+            Some((head, rest)) => {
+                self.children.entry(head.clone()).or_insert_with(PathNode::new).insert(rest, handler);
+            }
+        }
+    }
+}
+
+/// Walks a registered path pattern alongside the actual json structure, descending into matching
+/// objects and arrays until a node carrying a handler is reached.
+fn dispatch_path<'a,T: Iterator<Item=char>>(node: &mut PathNode<'a,T>, key: String, first: json::JsonEvent, parser: &mut json::Parser<T>) {
+    if node.handler.is_some() || node.children.is_empty() {
+        match node.handler {
+            Some(ref mut handler) => {
+                let context = PathContext::from_parser(parser);
+                handler(key, first, parser, &context)
+            },
+            None => { read_value(first, parser); },
+        }
+        return;
+    }
+
+    match first {
+        json::JsonEvent::ObjectStart => {
+            loop {
+                match parser.next() {
+                    None | Some(json::JsonEvent::ObjectEnd) => return,
+                    Some(token) => {
+                        let key = match parser.stack().top() {
+                            Some(json::StackElement::Key(k)) => k.to_string(),
+                            Some(thing) => panic!("invalid state: {:?}", thing),
+                            None => panic!("no stack???"),
+                        };
+                        match node.children.get_mut(&PathKey::Key(key.clone())) {
+                            Some(child) => dispatch_path(child, key, token, parser),
+                            None => { read_value(token, parser); },
+                        }
+                    }
+                }
+            }
+        },
+        json::JsonEvent::ArrayStart => {
+            loop {
+                match parser.next() {
+                    None | Some(json::JsonEvent::ArrayEnd) => return,
+                    Some(token) => {
+                        match node.children.get_mut(&PathKey::Index) {
+                            Some(child) => dispatch_path(child, String::new(), token, parser),
+                            None => { read_value(token, parser); },
+                        }
+                    }
+                }
+            }
+        },
+        _ => { read_value(first, parser); },
+    }
+}
+
 /// Reads a stream of chars and triggers callbacks when some values are detected.
 pub struct StreamReader<'a,T> {
     handlers: BTreeMap<String,Handler<'a,T>>,
+    path_handlers: PathNode<'a,T>,
     default_handler: Handler<'a,T>,
 }
 
@@ -47,6 +195,7 @@ impl <'a,T: Iterator<Item=char>> StreamReader<'a,T> {
     pub fn new() -> Self {
         StreamReader {
             handlers: BTreeMap::new(),
+            path_handlers: PathNode::new(),
             default_handler: dummy_handler(),
         }
     }
@@ -61,6 +210,16 @@ impl <'a,T: Iterator<Item=char>> StreamReader<'a,T> {
         self.handlers.insert(name, handler);
     }
 
+    /// Sets a handler for a value nested several objects/arrays deep, identified by a
+    /// dot/bracket path such as `"response.items[].name"`. An empty `[]` segment matches any
+    /// array index, so the handler above fires once per item in `items`.
+    ///
+    /// This lets callers extract a few deeply nested fields without writing the recursive
+    /// `StreamReader` chaining by hand.
+    pub fn set_handler_at(&mut self, path: &str, handler: Handler<'a,T>) {
+        let segments = parse_path(path);
+        self.path_handlers.insert(&segments, handler);
+    }
 
     /// Reads an entire object. It expects ObjectStart to have already been consumed, and it will
     /// consume ObjectEnd when found.
@@ -78,18 +237,114 @@ impl <'a,T: Iterator<Item=char>> StreamReader<'a,T> {
                         None => panic!("no stack???"),
                     };
                     // println!("Key was: {}", &key);
-                    let handler = self.handlers.get_mut(&key).unwrap_or(&mut self.default_handler);
-                    handler(key, token, parser);
+                    let context = PathContext::from_parser(parser);
+                    if self.handlers.contains_key(&key) {
+                        let handler = self.handlers.get_mut(&key).unwrap();
+                        handler(key, token, parser, &context);
+                    } else if let Some(child) = self.path_handlers.children.get_mut(&PathKey::Key(key.clone())) {
+                        dispatch_path(child, key, token, parser);
+                    } else {
+                        (self.default_handler)(key, token, parser, &context);
+                    }
                 }
             }
         }
     }
 }
 
+/// Reads a sequence of newline-delimited top-level JSON values (NDJSON), invoking a callback for
+/// each decoded document. Unlike `StreamReader`, this does not expect a single root object: it
+/// parses append-only JSON logs and streamed records that never terminate in a single container.
+pub struct NdjsonReader<'a> {
+    handler: Box<FnMut(json::Json)+'a>,
+}
+
+impl <'a> NdjsonReader<'a> {
+    /// Creates a new NdjsonReader that invokes `handler` once per line.
+    pub fn new<F: 'a+FnMut(json::Json)>(handler: F) -> Self {
+        NdjsonReader {
+            handler: Box::new(handler),
+        }
+    }
+
+    /// Reads an iterator of lines, parsing one top-level JSON value per non-empty line. A
+    /// malformed line is skipped rather than aborting the rest of the (potentially unbounded)
+    /// stream; the errors for skipped lines are returned so the caller can log or surface them.
+    pub fn read_stream<L: Iterator<Item=String>>(&mut self, lines: L) -> Vec<StreamError> {
+        let mut errors = Vec::new();
+
+        for line in lines {
+            let line = line.trim();
+            if line.is_empty() {
+                continue;
+            }
+
+            match json::Json::from_str(line) {
+                Ok(value) => (self.handler)(value),
+                Err(err) => errors.push(StreamError::from(err)),
+            }
+        }
+
+        errors
+    }
+}
+
+/// Reads a newline-delimited stream where each line is tagged as either `{"Header": {...}}` or
+/// `{"Buffer": {...}}`, mirroring how media pipelines transport timed JSON payloads line by line.
+///
+/// The header callback receives the header's fields. Each subsequent buffer callback receives the
+/// inner `data` value, plus the buffer's sibling fields (e.g. timestamps).
+pub struct FramedReader<'a> {
+    header_handler: Box<FnMut(json::Object)+'a>,
+    buffer_handler: Box<FnMut(json::Json,json::Object)+'a>,
+}
+
+impl <'a> FramedReader<'a> {
+    /// Creates a new FramedReader from a header callback and a buffer callback.
+    pub fn new<H,B>(header_handler: H, buffer_handler: B) -> Self
+        where H: 'a+FnMut(json::Object), B: 'a+FnMut(json::Json,json::Object) {
+        FramedReader {
+            header_handler: Box::new(header_handler),
+            buffer_handler: Box::new(buffer_handler),
+        }
+    }
+
+    /// Reads an iterator of lines, dispatching each `Header` or `Buffer` frame to the matching
+    /// callback. A line that isn't a well-formed frame is skipped rather than aborting the rest
+    /// of the (potentially unbounded) stream; the errors for skipped lines are returned so the
+    /// caller can log or surface them.
+    pub fn read_stream<L: Iterator<Item=String>>(&mut self, lines: L) -> Vec<StreamError> {
+        let mut errors = Vec::new();
+
+        for line in lines {
+            let line = line.trim();
+            if line.is_empty() {
+                continue;
+            }
+
+            let mut frame = match json::Json::from_str(line) {
+                Ok(json::Json::Object(object)) => object,
+                Ok(_) => { errors.push(StreamError::InvalidState); continue; },
+                Err(err) => { errors.push(StreamError::from(err)); continue; },
+            };
+
+            if let Some(json::Json::Object(header)) = frame.remove("Header") {
+                (self.header_handler)(header);
+            } else if let Some(json::Json::Object(mut buffer)) = frame.remove("Buffer") {
+                let data = buffer.remove("data").unwrap_or(json::Json::Null);
+                (self.buffer_handler)(data, buffer);
+            } else {
+                errors.push(StreamError::InvalidState);
+            }
+        }
+
+        errors
+    }
+}
 
 /// Creates a handler that reads every value it finds, and copies it into the given target.
 pub fn copy_handler<'a,T:Iterator<Item=char>>(target: &'a mut json::Object) -> Handler<'a,T> {
-    Box::new(move |key,first,parser| {
+    Box::new(move |key,first,parser,_| {
         target.insert(key, read_value(first,parser));
     })
 }
@@ -98,7 +353,7 @@ pub fn copy_handler<'a,T:Iterator<Item=char>>(target: &'a mut json::Object) -> H
 ///
 /// Defers actual handling to the given function.
 pub fn array_handler<'a,F: 'a+FnMut(json::Json),T:Iterator<Item=char>>(mut object_handler: F) -> Handler<'a,T> {
-    Box::new(move |_,first,parser| {
+    Box::new(move |_,first,parser,_| {
         if first != json::JsonEvent::ArrayStart {
             panic!("non-array found");
         }
@@ -113,6 +368,49 @@ pub fn array_handler<'a,F: 'a+FnMut(json::Json),T:Iterator<Item=char>>(mut objec
     })
 }
 
+/// A borrowing, pull-based iterator over the elements of a top-level array, yielding one
+/// `json::Json` at a time instead of buffering the whole array like `read_array` does. This
+/// gives flyweight-style enumeration over huge arrays (e.g. millions of records) without ever
+/// materializing the whole `Vec`.
+///
+/// Because it borrows the underlying `json::Parser`, this can't implement the standard
+/// `Iterator` trait with a fully generic item; use `next()` directly in a `while let` loop.
+pub struct ArrayValues<'p,T:'p> {
+    parser: &'p mut json::Parser<T>,
+    done: bool,
+}
+
+impl <'p,T: Iterator<Item=char>> ArrayValues<'p,T> {
+    /// Reads the next element of the array, or `None` once `ArrayEnd` is reached.
+    pub fn next(&mut self) -> Option<json::Json> {
+        if self.done {
+            return None;
+        }
+
+        match self.parser.next() {
+            None | Some(json::JsonEvent::ArrayEnd) => {
+                self.done = true;
+                None
+            },
+            Some(token) => Some(read_value(token, self.parser)),
+        }
+    }
+}
+
+/// Starts a flyweight-style enumeration over a top-level array, keeping only a single element in
+/// memory at a time. It expects `first` to be `json::JsonEvent::ArrayStart`, and consumes
+/// `ArrayEnd` automatically once the returned `ArrayValues` is exhausted.
+pub fn read_array_stream<'p,T: Iterator<Item=char>>(first: json::JsonEvent, parser: &'p mut json::Parser<T>) -> ArrayValues<'p,T> {
+    if first != json::JsonEvent::ArrayStart {
+        panic!("non-array found");
+    }
+
+    ArrayValues {
+        parser: parser,
+        done: false,
+    }
+}
+
 /// Reads a complete value from the stream.
 pub fn read_value<T: Iterator<Item=char>>(first: json::JsonEvent, parser: &mut json::Parser<T>) -> json::Json {
     // println!("Reading from {:?}", first);
@@ -129,11 +427,35 @@ pub fn read_value<T: Iterator<Item=char>>(first: json::JsonEvent, parser: &mut j
     }
 }
 
+/// Decodes exactly one value subtree out of the stream into a user type implementing
+/// `rustc_serialize::Decodable`. This lets callers mix cheap streaming skips for irrelevant keys
+/// with ergonomic typed extraction for the handful of keys they actually care about, instead of
+/// manually walking the `json::Json` enum by hand.
+pub fn read_typed<T: Iterator<Item=char>, D: rustc_serialize::Decodable>(first: json::JsonEvent, parser: &mut json::Parser<T>) -> json::DecodeResult<D> {
+    let value = read_value(first, parser);
+    let mut decoder = json::Decoder::new(value);
+    rustc_serialize::Decodable::decode(&mut decoder)
+}
+
+/// Builds a handler that decodes its value into `D` and passes it to the given function.
+///
+/// Panics if the subtree doesn't decode into `D`. Use `try_typed_handler` with a
+/// `TryStreamReader` instead when decoding untrusted input that might not match `D`'s shape.
+pub fn typed_handler<'a,D: rustc_serialize::Decodable,F: 'a+FnMut(D),T:Iterator<Item=char>>(mut handler: F) -> Handler<'a,T> {
+    Box::new(move |_,first,parser,_| {
+        match read_typed(first, parser) {
+            Ok(value) => handler(value),
+            Err(err) => panic!("failed to decode value: {:?}", err),
+        }
+    })
+}
+
 /// Reads a complete array from the stream.
 pub fn read_array<T: Iterator<Item=char>>(parser: &mut json::Parser<T>) -> json::Array {
     let mut result = json::Array::new();
-    // We don't really care about the key here, so String::new() is enough
-    array_handler(|item| result.push(item))(String::new(), json::JsonEvent::ArrayStart, parser);
+    // We don't really care about the key or path here, so String::new()/a fresh context is enough
+    let context = PathContext::from_parser(parser);
+    array_handler(|item| result.push(item))(String::new(), json::JsonEvent::ArrayStart, parser, &context);
     result
 }
 
@@ -143,7 +465,7 @@ pub fn read_object<T: Iterator<Item=char>>(parser: &mut json::Parser<T>) -> json
 
     {
         let mut reader = StreamReader::new();
-        reader.set_default_handler(Box::new(|name,first,parser| {
+        reader.set_default_handler(Box::new(|name,first,parser,_| {
             result.insert(name, read_value(first,parser));
         }));
         reader.read_object(parser);
@@ -151,3 +473,320 @@ pub fn read_object<T: Iterator<Item=char>>(parser: &mut json::Parser<T>) -> json
 
     result
 }
+
+/// Errors produced by the fallible (`try_*`) reading API, used when parsing untrusted input that
+/// should not cause the process to panic on malformed json.
+#[derive(Debug)]
+pub enum StreamError {
+    /// A value was found where it wasn't expected, e.g. a non-array given to `try_array_handler`.
+    UnexpectedToken(json::JsonEvent),
+    /// The stream ended before the current value was fully read.
+    UnexpectedEndOfStream,
+    /// The parser stack was not in a state this reader knows how to handle. `FramedReader` also
+    /// reports a line that isn't a `Header` or `Buffer` frame this way.
+    InvalidState,
+    /// An error surfaced directly by the underlying `json::Parser`.
+    Parser(json::ParserError),
+    /// A subtree didn't decode into the type requested from `try_typed_handler`.
+    Decoder(json::DecoderError),
+}
+
+impl From<json::ParserError> for StreamError {
+    fn from(err: json::ParserError) -> Self {
+        StreamError::Parser(err)
+    }
+}
+
+impl From<json::DecoderError> for StreamError {
+    fn from(err: json::DecoderError) -> Self {
+        StreamError::Decoder(err)
+    }
+}
+
+/// Fallible counterpart of `Handler`: must consume the json stream the same way, but may report
+/// an error instead of panicking.
+pub type TryHandler<'a,T> = Box<FnMut(String, json::JsonEvent, &mut json::Parser<T>) -> Result<(),StreamError>+'a>;
+
+/// Creates a dummy fallible handler that just consumes the given value.
+pub fn try_dummy_handler<'a,T: Iterator<Item=char>>() -> TryHandler<'a,T> {
+    Box::new(|_,first,parser| {
+        try_read_value(first, parser).map(|_| ())
+    })
+}
+
+/// Fallible counterpart of `StreamReader`: reports malformed input as a `StreamError` instead of
+/// panicking, so a caller parsing untrusted network input can recover gracefully.
+pub struct TryStreamReader<'a,T> {
+    handlers: BTreeMap<String,TryHandler<'a,T>>,
+    default_handler: TryHandler<'a,T>,
+}
+
+impl <'a,T: Iterator<Item=char>> TryStreamReader<'a,T> {
+    /// Creates a new TryStreamReader.
+    pub fn new() -> Self {
+        TryStreamReader {
+            handlers: BTreeMap::new(),
+            default_handler: try_dummy_handler(),
+        }
+    }
+
+    /// Sets the default handler for unregistered keys.
+    pub fn set_default_handler(&mut self, handler: TryHandler<'a,T>) {
+        self.default_handler = handler;
+    }
+
+    /// Sets a specific handler for when the given key is found.
+    pub fn set_handler(&mut self, name: String, handler: TryHandler<'a,T>) {
+        self.handlers.insert(name, handler);
+    }
+
+    /// Reads an entire object, the fallible way. It expects ObjectStart to have already been
+    /// consumed, and will consume ObjectEnd when found. Stops at the first error encountered.
+    pub fn try_read_object(&mut self, parser: &mut json::Parser<T>) -> Result<(),StreamError> {
+        loop {
+            match parser.next() {
+                None => return Err(StreamError::UnexpectedEndOfStream),
+                Some(json::JsonEvent::ObjectEnd) => return Ok(()),
+                Some(json::JsonEvent::Error(err)) => return Err(StreamError::Parser(err)),
+                Some(token) => {
+                    let key = match parser.stack().top() {
+                        Some(json::StackElement::Key(k)) => k.to_string(),
+                        Some(_) | None => return Err(StreamError::InvalidState),
+                    };
+
+                    if self.handlers.contains_key(&key) {
+                        let handler = self.handlers.get_mut(&key).unwrap();
+                        handler(key, token, parser)?;
+                    } else {
+                        (self.default_handler)(key, token, parser)?;
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Reads a complete value from the stream, the fallible way.
+pub fn try_read_value<T: Iterator<Item=char>>(first: json::JsonEvent, parser: &mut json::Parser<T>) -> Result<json::Json,StreamError> {
+    match first {
+        json::JsonEvent::ObjectStart => Ok(json::Json::Object(try_read_object(parser)?)),
+        json::JsonEvent::ArrayStart => Ok(json::Json::Array(try_read_array(parser)?)),
+        json::JsonEvent::BooleanValue(b) => Ok(json::Json::Boolean(b)),
+        json::JsonEvent::I64Value(i) => Ok(json::Json::I64(i)),
+        json::JsonEvent::U64Value(u) => Ok(json::Json::U64(u)),
+        json::JsonEvent::F64Value(f) => Ok(json::Json::F64(f)),
+        json::JsonEvent::StringValue(s) => Ok(json::Json::String(s)),
+        json::JsonEvent::NullValue => Ok(json::Json::Null),
+        json::JsonEvent::Error(err) => Err(StreamError::Parser(err)),
+        token => Err(StreamError::UnexpectedToken(token)),
+    }
+}
+
+/// Builds a fallible handler that only reads arrays, returning an error if another value is
+/// found. Defers actual handling to the given function.
+pub fn try_array_handler<'a,F: 'a+FnMut(json::Json),T:Iterator<Item=char>>(mut object_handler: F) -> TryHandler<'a,T> {
+    Box::new(move |_,first,parser| {
+        if first != json::JsonEvent::ArrayStart {
+            return Err(StreamError::UnexpectedToken(first));
+        }
+
+        loop {
+            match parser.next() {
+                None => return Err(StreamError::UnexpectedEndOfStream),
+                Some(json::JsonEvent::ArrayEnd) => return Ok(()),
+                Some(json::JsonEvent::Error(err)) => return Err(StreamError::Parser(err)),
+                Some(token) => object_handler(try_read_value(token, parser)?),
+            }
+        }
+    })
+}
+
+/// Reads a complete array from the stream, the fallible way.
+pub fn try_read_array<T: Iterator<Item=char>>(parser: &mut json::Parser<T>) -> Result<json::Array,StreamError> {
+    let mut result = json::Array::new();
+    try_array_handler(|item| result.push(item))(String::new(), json::JsonEvent::ArrayStart, parser)?;
+    Ok(result)
+}
+
+/// Reads a complete object from the stream, the fallible way.
+pub fn try_read_object<T: Iterator<Item=char>>(parser: &mut json::Parser<T>) -> Result<json::Object,StreamError> {
+    let mut result = json::Object::new();
+
+    {
+        let mut reader = TryStreamReader::new();
+        reader.set_default_handler(Box::new(|name,first,parser| {
+            let value = try_read_value(first, parser)?;
+            result.insert(name, value);
+            Ok(())
+        }));
+        reader.try_read_object(parser)?;
+    }
+
+    Ok(result)
+}
+
+/// Fallible counterpart of `typed_handler`, for use with `TryStreamReader`. Surfaces a decode
+/// failure as a `StreamError` instead of panicking.
+pub fn try_typed_handler<'a,D: rustc_serialize::Decodable,F: 'a+FnMut(D),T:Iterator<Item=char>>(mut handler: F) -> TryHandler<'a,T> {
+    Box::new(move |_,first,parser| {
+        let value = try_read_value(first, parser)?;
+        let mut decoder = json::Decoder::new(value);
+        let decoded = rustc_serialize::Decodable::decode(&mut decoder)?;
+        handler(decoded);
+        Ok(())
+    })
+}
+
+/// Wraps a `Chars` iterator to track how many characters have been pulled out of it, and whether
+/// the most recent pull actually produced one, so `StreamDecoder::feed` can tell how much of its
+/// buffer a parse attempt actually consumed.
+struct CountingChars<'c> {
+    chars: ::std::str::Chars<'c>,
+    count: Rc<Cell<usize>>,
+    last_was_some: Rc<Cell<bool>>,
+}
+
+impl <'c> Iterator for CountingChars<'c> {
+    type Item = char;
+
+    fn next(&mut self) -> Option<char> {
+        let next = self.chars.next();
+        self.last_was_some.set(next.is_some());
+        if next.is_some() {
+            self.count.set(self.count.get() + 1);
+        }
+        next
+    }
+}
+
+/// Whether a `StreamError` means the input seen so far is merely incomplete (and more bytes
+/// would let the parser make progress), as opposed to being genuinely malformed.
+fn needs_more_input(err: &StreamError) -> bool {
+    match *err {
+        StreamError::UnexpectedEndOfStream => true,
+        StreamError::Parser(ref parser_err) => match *parser_err {
+            json::ParserError::SyntaxError(code, _, _) => match code {
+                json::ErrorCode::EOFWhileParsingObject |
+                json::ErrorCode::EOFWhileParsingArray |
+                json::ErrorCode::EOFWhileParsingValue |
+                json::ErrorCode::EOFWhileParsingString => true,
+                _ => false,
+            },
+            _ => false,
+        },
+        _ => false,
+    }
+}
+
+/// Outcome of a single `StreamDecoder::feed` call.
+#[derive(Debug)]
+pub enum FeedResult {
+    /// This many complete top-level values were parsed out of the buffered input and dispatched.
+    Dispatched(usize),
+    /// No complete value could be parsed out of the buffered input yet; more bytes are needed.
+    NeedsMoreInput,
+    /// The buffered input is genuinely malformed, as opposed to merely incomplete. The buffer has
+    /// been discarded so the decoder can resynchronize on whatever is fed next.
+    Error(StreamError),
+}
+
+/// A push decoder for callers reading from a socket or other non-blocking source, which can't
+/// hand this crate a synchronous `Iterator<Item=char>` up front.
+///
+/// Bytes are fed incrementally via `feed`, which drives the underlying parser forward only as far
+/// as the currently buffered input allows, dispatching `handler` for each complete top-level value
+/// recognized and retaining any partial value across calls. This can be wrapped in a
+/// `tokio_util::codec::Decoder` or polled from a futures `Stream` without any internal blocking.
+pub struct StreamDecoder<'a> {
+    buffer: String,
+    handler: Box<FnMut(json::Json)+'a>,
+}
+
+impl <'a> StreamDecoder<'a> {
+    /// Creates a new StreamDecoder that invokes `handler` once per complete top-level value.
+    pub fn new<F: 'a+FnMut(json::Json)>(handler: F) -> Self {
+        StreamDecoder {
+            buffer: String::new(),
+            handler: Box::new(handler),
+        }
+    }
+
+    /// Feeds a chunk of bytes into the decoder. Invalid utf-8 is replaced lossily.
+    ///
+    /// Returns how many complete top-level values were dispatched this call,
+    /// `FeedResult::NeedsMoreInput` if the buffered input ends in the middle of a value, or
+    /// `FeedResult::Error` if the buffered input is genuinely malformed (in which case the
+    /// buffer is discarded so the decoder can resynchronize on whatever is fed next).
+    pub fn feed(&mut self, bytes: &[u8]) -> FeedResult {
+        self.buffer.push_str(&String::from_utf8_lossy(bytes));
+
+        // How many bytes at the front of `self.buffer` have been fully consumed so far. Only
+        // drained once at the end, rather than after every value, so dispatching N values out of
+        // one chunk costs O(chunk length) rather than O(chunk length * N).
+        let mut position = 0;
+        let mut dispatched = 0;
+
+        loop {
+            let count = Rc::new(Cell::new(0));
+            let last_was_some = Rc::new(Cell::new(false));
+            let chars = CountingChars {
+                chars: self.buffer[position..].chars(),
+                count: count.clone(),
+                last_was_some: last_was_some.clone(),
+            };
+            let mut parser = json::Parser::new(chars);
+
+            let first = match parser.next() {
+                None => break,
+                Some(json::JsonEvent::Error(err)) => {
+                    let err = StreamError::from(err);
+                    // An error raised after the parser ran off the end of the buffer means the
+                    // input stops partway through a token (e.g. `tr` or `1.`), not that it's malformed.
+                    if needs_more_input(&err) || !last_was_some.get() {
+                        break;
+                    }
+                    self.buffer.clear();
+                    return FeedResult::Error(err);
+                },
+                Some(token) => token,
+            };
+
+            match try_read_value(first, &mut parser) {
+                Ok(value) => {
+                    // A top-level number running to the very end of the buffer may continue in
+                    // the next chunk, so it's kept until a following character ends it.
+                    if value.is_number() && !last_was_some.get() {
+                        break;
+                    }
+                    // `json::Parser` always looks one character ahead of the value it just
+                    // finished. If that lookahead pulled a real character (rather than hitting
+                    // the end of our buffer), it belongs to whatever comes next and must be left
+                    // in the buffer rather than counted as consumed.
+                    let mut consumed_chars = count.get();
+                    if last_was_some.get() {
+                        consumed_chars -= 1;
+                    }
+                    let consumed: usize = self.buffer[position..].chars().take(consumed_chars).map(|c| c.len_utf8()).sum();
+                    position += consumed;
+                    dispatched += 1;
+                    (self.handler)(value);
+                },
+                Err(err) => {
+                    if needs_more_input(&err) || !last_was_some.get() {
+                        break;
+                    }
+                    self.buffer.clear();
+                    return FeedResult::Error(err);
+                },
+            }
+        }
+
+        self.buffer.drain(..position);
+
+        if dispatched > 0 {
+            FeedResult::Dispatched(dispatched)
+        } else {
+            FeedResult::NeedsMoreInput
+        }
+    }
+}